@@ -0,0 +1,85 @@
+//! Authorization: either the interactive phone/code/2FA flow, or a
+//! non-interactive bot-token sign in, picked from a single entry point.
+
+use crate::{prompt, Result};
+use grammers_client::{Client, SignInError};
+
+/// How to authorize the client when it doesn't already have a session.
+pub enum AuthMethod {
+    /// Prompt for a phone number, login code, and (if enabled) 2FA password.
+    Interactive,
+    /// Sign in as a bot using the given bot token, no prompts involved.
+    BotToken(String),
+}
+
+impl AuthMethod {
+    /// Picks [`AuthMethod::BotToken`] when `bot_token` is set (e.g. from
+    /// [`crate::config::AppConfig::bot_token`]), falling back to
+    /// [`AuthMethod::Interactive`] otherwise.
+    pub fn new(bot_token: Option<String>) -> Self {
+        match bot_token {
+            Some(token) if !token.is_empty() => AuthMethod::BotToken(token),
+            _ => AuthMethod::Interactive,
+        }
+    }
+}
+
+/// Authorizes `client` using `method`, returning once signed in. Does not
+/// save the session; callers are expected to do that afterwards the same
+/// way regardless of which method was used.
+pub async fn sign_in(client: &mut Client, api_id: i32, api_hash: &str, method: &AuthMethod) -> Result<()> {
+    match method {
+        AuthMethod::Interactive => sign_in_interactive(client, api_id, api_hash).await,
+        AuthMethod::BotToken(token) => sign_in_bot(client, token).await,
+    }
+}
+
+async fn sign_in_interactive(client: &mut Client, api_id: i32, api_hash: &str) -> Result<()> {
+    let phone = prompt("Enter your phone number (international format): ")?;
+    let token = client.request_login_code(&phone, api_id, api_hash).await?;
+    let code = prompt("Enter the code you received: ")?;
+    let signed_in = client.sign_in(&token, &code).await;
+    match signed_in {
+        Err(SignInError::PasswordRequired(password_token)) => {
+            // Note: this `prompt` method will echo the password in the console.
+            //       Real code might want to use a better way to handle this.
+            let hint = password_token.hint().unwrap();
+            let prompt_message = format!("Enter the password (hint {}): ", &hint);
+            let password = prompt(prompt_message.as_str())?;
+
+            client
+                .check_password(password_token, password.trim())
+                .await?;
+        }
+        Ok(_) => (),
+        Err(e) => panic!("{}", e),
+    };
+    Ok(())
+}
+
+async fn sign_in_bot(client: &mut Client, token: &str) -> Result<()> {
+    client.bot_sign_in(token).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_picks_bot_token_when_set() {
+        assert!(matches!(
+            AuthMethod::new(Some("123:abc".to_string())),
+            AuthMethod::BotToken(token) if token == "123:abc"
+        ));
+    }
+
+    #[test]
+    fn new_falls_back_to_interactive_when_absent_or_empty() {
+        assert!(matches!(AuthMethod::new(None), AuthMethod::Interactive));
+        assert!(matches!(
+            AuthMethod::new(Some(String::new())),
+            AuthMethod::Interactive
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! Runtime configuration, loaded from a TOML file and falling back to
+//! environment variables when the file is absent. Replaces the old
+//! compile-time `env!("TG_ID")`/`env!("TG_HASH")` and the hardcoded SOCKS5
+//! proxy URL.
+
+use crate::Result;
+use serde::Deserialize;
+use std::{env, fs, path::Path};
+
+/// Parsed application configuration. See [`AppConfig::parse`] for how the
+/// fields are sourced.
+#[derive(Debug, Deserialize)]
+pub struct AppConfig {
+    pub api_id: i32,
+    pub api_hash: String,
+    pub bot_token: Option<String>,
+    #[serde(default = "default_session_file")]
+    pub session_file: String,
+    pub proxy_url: Option<String>,
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Chat ids to bridge together when relay mode is enabled. Omit or
+    /// leave empty to run without relaying.
+    #[serde(default)]
+    pub bridge_chats: Vec<i64>,
+}
+
+fn default_session_file() -> String {
+    "app.session".to_string()
+}
+
+fn default_log_level() -> String {
+    "warn".to_string()
+}
+
+impl AppConfig {
+    /// Loads config from the TOML file at `path` if it exists, otherwise
+    /// falls back to environment variables (`TG_ID`, `TG_HASH`, `BOT_TOKEN`,
+    /// `SESSION_FILE`, `PROXY_URL`, `RUST_LOG`, `BRIDGE_CHATS`). Returns a
+    /// descriptive error rather than panicking when a required field is
+    /// missing or invalid.
+    pub fn parse(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let config = if path.exists() {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            toml::from_str(&contents)
+                .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?
+        } else {
+            Self::from_env()?
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_env() -> Result<Self> {
+        let api_id = env::var("TG_ID")
+            .map_err(|_| "TG_ID must be set when no config file is present")?
+            .parse()
+            .map_err(|_| "TG_ID must be a valid integer")?;
+        let api_hash =
+            env::var("TG_HASH").map_err(|_| "TG_HASH must be set when no config file is present")?;
+
+        let bridge_chats = env::var("BRIDGE_CHATS")
+            .ok()
+            .map(|ids| {
+                ids.split(',')
+                    .filter(|s| !s.trim().is_empty())
+                    .map(|id| id.trim().parse())
+                    .collect::<std::result::Result<Vec<i64>, _>>()
+                    .map_err(|_| "BRIDGE_CHATS must be a comma-separated list of chat ids")
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(AppConfig {
+            api_id,
+            api_hash,
+            bot_token: env::var("BOT_TOKEN").ok(),
+            session_file: env::var("SESSION_FILE").unwrap_or_else(|_| default_session_file()),
+            proxy_url: env::var("PROXY_URL").ok(),
+            log_level: env::var("RUST_LOG").unwrap_or_else(|_| default_log_level()),
+            bridge_chats,
+        })
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.api_hash.trim().is_empty() {
+            return Err("api_hash must not be empty".into());
+        }
+        Ok(())
+    }
+
+    /// Parses `log_level` into a [`log::LevelFilter`], defaulting to `Warn`
+    /// if it isn't a recognized level name.
+    pub fn log_level_filter(&self) -> log::LevelFilter {
+        self.log_level.parse().unwrap_or(log::LevelFilter::Warn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // `from_env` reads process-global environment variables, so tests that
+    // touch them must not run concurrently with each other.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn base_config() -> AppConfig {
+        AppConfig {
+            api_id: 1,
+            api_hash: "hash".to_string(),
+            bot_token: None,
+            session_file: default_session_file(),
+            proxy_url: None,
+            log_level: default_log_level(),
+            bridge_chats: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn validate_rejects_empty_api_hash() {
+        let mut config = base_config();
+        config.api_hash = "  ".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_accepts_non_empty_api_hash() {
+        assert!(base_config().validate().is_ok());
+    }
+
+    #[test]
+    fn from_env_requires_tg_id_and_tg_hash() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        env::remove_var("TG_ID");
+        env::remove_var("TG_HASH");
+        env::remove_var("BRIDGE_CHATS");
+        assert!(AppConfig::from_env().is_err());
+    }
+
+    #[test]
+    fn from_env_parses_bridge_chats() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        env::set_var("TG_ID", "123");
+        env::set_var("TG_HASH", "hash");
+        env::set_var("BRIDGE_CHATS", "10, 20,30");
+
+        let config = AppConfig::from_env().unwrap();
+        assert_eq!(config.api_id, 123);
+        assert_eq!(config.bridge_chats, vec![10, 20, 30]);
+
+        env::remove_var("TG_ID");
+        env::remove_var("TG_HASH");
+        env::remove_var("BRIDGE_CHATS");
+    }
+
+    #[test]
+    fn from_env_rejects_malformed_bridge_chats() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        env::set_var("TG_ID", "123");
+        env::set_var("TG_HASH", "hash");
+        env::set_var("BRIDGE_CHATS", "not-a-number");
+
+        assert!(AppConfig::from_env().is_err());
+
+        env::remove_var("TG_ID");
+        env::remove_var("TG_HASH");
+        env::remove_var("BRIDGE_CHATS");
+    }
+}
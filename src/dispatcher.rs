@@ -0,0 +1,253 @@
+//! A small handler-registry dispatcher, modeled on the builder + handler
+//! registry pattern used by bot frameworks built on top of grammers (e.g.
+//! `teleser`). Instead of a single hard-coded `while let Some(update) = ...`
+//! loop, users register typed handlers against update variants, optionally
+//! narrowed by a [`Filter`], and the [`Dispatcher`] fans updates out to them
+//! concurrently.
+
+use crate::reconnect::ClientWatch;
+use crate::Result;
+use grammers_client::types::Chat;
+use grammers_client::{Client, Update};
+use log::{error, warn};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::task;
+
+type BoxFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// The update variant a route should be matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateKind {
+    NewMessage,
+    MessageEdited,
+    CallbackQuery,
+    /// Anything not covered by the variants above, matched regardless of
+    /// payload (raw updates, message deletions, etc).
+    Raw,
+}
+
+impl UpdateKind {
+    fn of(update: &Update) -> Self {
+        match update {
+            Update::NewMessage(_) => UpdateKind::NewMessage,
+            Update::MessageEdited(_) => UpdateKind::MessageEdited,
+            Update::CallbackQuery(_) => UpdateKind::CallbackQuery,
+            _ => UpdateKind::Raw,
+        }
+    }
+}
+
+/// A predicate over an incoming update, used to narrow a route beyond its
+/// [`UpdateKind`] (chat kind, command prefix, text regex, ...).
+pub type Filter = Arc<dyn Fn(&Update) -> bool + Send + Sync>;
+
+/// Matches updates whose chat satisfies `pred`, e.g. `Chat::Group(_)`.
+pub fn chat_kind(pred: impl Fn(&Chat) -> bool + Send + Sync + 'static) -> Filter {
+    Arc::new(move |update| match update {
+        Update::NewMessage(message) | Update::MessageEdited(message) => pred(&message.chat()),
+        _ => false,
+    })
+}
+
+/// Matches `NewMessage`/`MessageEdited` updates whose text starts with
+/// `prefix` (e.g. `"/start"`).
+pub fn command(prefix: &'static str) -> Filter {
+    Arc::new(move |update| match update {
+        Update::NewMessage(message) | Update::MessageEdited(message) => {
+            matches_prefix(message.text(), prefix)
+        }
+        _ => false,
+    })
+}
+
+/// The text-matching half of [`command`], pulled out so it can be unit
+/// tested directly: the filter closures above can't be exercised from a
+/// unit test since `Update`'s message-carrying variants wrap a live
+/// grammers `Message` that can only be constructed from an active
+/// connection.
+fn matches_prefix(text: &str, prefix: &str) -> bool {
+    text.starts_with(prefix)
+}
+
+/// Matches `NewMessage`/`MessageEdited` updates whose text matches `re`.
+#[cfg(feature = "regex-filters")]
+pub fn text_regex(re: regex::Regex) -> Filter {
+    Arc::new(move |update| match update {
+        Update::NewMessage(message) | Update::MessageEdited(message) => re.is_match(message.text()),
+        _ => false,
+    })
+}
+
+/// A single registered update handler. Implemented for any
+/// `Fn(Client, Arc<Update>) -> impl Future<Output = Result<()>>`, so closures
+/// can be registered directly without an explicit trait impl. The update is
+/// shared via `Arc` rather than cloned, since the same update may be handed
+/// to several matching routes at once.
+pub trait Handler: Send + Sync + 'static {
+    fn handle(&self, client: Client, update: Arc<Update>) -> BoxFuture;
+}
+
+impl<F, Fut> Handler for F
+where
+    F: Fn(Client, Arc<Update>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    fn handle(&self, client: Client, update: Arc<Update>) -> BoxFuture {
+        Box::pin(self(client, update))
+    }
+}
+
+struct Route {
+    kind: UpdateKind,
+    filter: Option<Filter>,
+    handler: Arc<dyn Handler>,
+}
+
+/// Builds a [`Dispatcher`] by registering handlers against update kinds,
+/// each optionally narrowed with [`DispatcherBuilder::filter`].
+pub struct DispatcherBuilder {
+    client: ClientWatch,
+    routes: Vec<Route>,
+}
+
+impl DispatcherBuilder {
+    pub fn new(client: ClientWatch) -> Self {
+        DispatcherBuilder {
+            client,
+            routes: Vec::new(),
+        }
+    }
+
+    fn on(mut self, kind: UpdateKind, handler: impl Handler) -> Self {
+        self.routes.push(Route {
+            kind,
+            filter: None,
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn on_new_message(self, handler: impl Handler) -> Self {
+        self.on(UpdateKind::NewMessage, handler)
+    }
+
+    pub fn on_message_edited(self, handler: impl Handler) -> Self {
+        self.on(UpdateKind::MessageEdited, handler)
+    }
+
+    pub fn on_callback_query(self, handler: impl Handler) -> Self {
+        self.on(UpdateKind::CallbackQuery, handler)
+    }
+
+    pub fn on_raw_update(self, handler: impl Handler) -> Self {
+        self.on(UpdateKind::Raw, handler)
+    }
+
+    /// Attaches `filter` to the route that was registered last. Panics if
+    /// called before any `on_*` call, since that would indicate a bug in the
+    /// caller rather than something recoverable.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.routes
+            .last_mut()
+            .expect("filter() called before any handler was registered")
+            .filter = Some(filter);
+        self
+    }
+
+    pub fn build(self) -> Dispatcher {
+        Dispatcher {
+            client: self.client,
+            routes: self.routes,
+        }
+    }
+}
+
+/// Pulls updates from the current `Client` handle and fans each one out to
+/// every matching route, spawning each handler invocation as its own task
+/// so a slow handler (e.g. one awaiting `get_full_user`) doesn't stall the
+/// rest. Handler errors are logged and the loop continues.
+///
+/// A disconnect (`next_update` returning `Ok(None)` or an error) does not
+/// end the loop: `reconnect::run_supervised` is the component responsible
+/// for giving up, so this just waits for it to publish a new `Client` on
+/// the `watch` channel and picks back up from there. The loop only returns
+/// once the channel itself closes, i.e. once the supervisor task has
+/// exited for good.
+pub struct Dispatcher {
+    client: ClientWatch,
+    routes: Vec<Route>,
+}
+
+impl Dispatcher {
+    pub fn builder(client: ClientWatch) -> DispatcherBuilder {
+        DispatcherBuilder::new(client)
+    }
+
+    /// Runs until the `watch` channel publishing the live `Client` is
+    /// closed, i.e. until `reconnect::run_supervised` has given up and
+    /// dropped its sender.
+    pub async fn run(mut self) -> Result<()> {
+        loop {
+            let mut client = self.client.borrow().clone();
+            let update = match client.next_update().await {
+                Ok(Some(update)) => update,
+                Ok(None) => {
+                    warn!("connection closed, waiting for a reconnect");
+                    if self.client.changed().await.is_err() {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Err(error) => {
+                    warn!("next_update failed, waiting for a reconnect: {}", error);
+                    if self.client.changed().await.is_err() {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            };
+            let kind = UpdateKind::of(&update);
+            let update = Arc::new(update);
+            for route in &self.routes {
+                if route.kind != kind {
+                    continue;
+                }
+                if let Some(filter) = &route.filter {
+                    if !filter(&update) {
+                        continue;
+                    }
+                }
+                let client = client.clone();
+                let handler = route.handler.clone();
+                let update = update.clone();
+                task::spawn(async move {
+                    if let Err(error) = handler.handle(client, update).await {
+                        error!("handler failed: {}", error);
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_prefix_checks_command_prefix() {
+        assert!(matches_prefix("/start", "/start"));
+        assert!(matches_prefix("/start@mybot extra args", "/start"));
+        assert!(!matches_prefix("hello /start", "/start"));
+        assert!(!matches_prefix("", "/start"));
+    }
+
+    #[test]
+    fn update_kind_variants_are_distinct() {
+        assert_ne!(UpdateKind::NewMessage, UpdateKind::Raw);
+        assert_ne!(UpdateKind::MessageEdited, UpdateKind::CallbackQuery);
+        assert_eq!(UpdateKind::Raw, UpdateKind::Raw);
+    }
+}
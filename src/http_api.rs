@@ -0,0 +1,133 @@
+//! Embedded HTTP control API (feature `http-api`): exposes the crate's
+//! invoke helpers over REST, starting with `get_full_user`/`get_users`, so
+//! operators have an out-of-band way to trigger lookups without editing the
+//! update loop. Handlers re-read the current `Client` from a `watch` channel
+//! on every request rather than holding a single clone for the server's
+//! whole lifetime, so a reconnect doesn't leave the API stuck on a dead
+//! connection.
+
+use crate::reconnect::ClientWatch;
+use crate::Result;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json};
+use axum::routing::get;
+use axum::Router;
+use grammers_session::PackedChat;
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+#[derive(Clone)]
+struct ApiState {
+    client: ClientWatch,
+}
+
+#[derive(Deserialize)]
+struct FullUserQuery {
+    /// Base64-encoded packed peer bytes, the same format `message.chat().pack().to_bytes()`
+    /// produces.
+    id: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+fn packed_user_from_query(id: &str) -> std::result::Result<tl::enums::InputUser, String> {
+    let bytes = base64::decode(id).map_err(|e| format!("invalid base64 id: {}", e))?;
+    let packed = PackedChat::from_bytes(&bytes).map_err(|e| format!("invalid packed id: {}", e))?;
+    packed
+        .try_to_input_user()
+        .ok_or_else(|| "id does not refer to a user".to_string())
+}
+
+async fn get_full_user_handler(
+    State(state): State<ApiState>,
+    Query(query): Query<FullUserQuery>,
+) -> impl IntoResponse {
+    let input_user = match packed_user_from_query(&query.id) {
+        Ok(input_user) => input_user,
+        Err(error) => return (StatusCode::BAD_REQUEST, Json(ErrorBody { error })).into_response(),
+    };
+
+    let client = state.client.borrow().clone();
+    match crate::get_full_user(client, input_user).await {
+        Ok(user_full) => Json(user_full).into_response(),
+        Err(error) => (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorBody {
+                error: error.to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct UsersQuery {
+    /// Comma-separated list of base64-encoded packed peer bytes.
+    ids: String,
+}
+
+async fn get_users_handler(State(state): State<ApiState>, Query(query): Query<UsersQuery>) -> impl IntoResponse {
+    let mut input_users = Vec::new();
+    for id in query.ids.split(',').filter(|s| !s.is_empty()) {
+        match packed_user_from_query(id) {
+            Ok(input_user) => input_users.push(input_user),
+            Err(error) => return (StatusCode::BAD_REQUEST, Json(ErrorBody { error })).into_response(),
+        }
+    }
+
+    let client = state.client.borrow().clone();
+    match crate::get_users(client, input_users).await {
+        Ok(users) => Json(users).into_response(),
+        Err(error) => (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorBody {
+                error: error.to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+fn router(client: ClientWatch) -> Router {
+    Router::new()
+        .route("/users/full", get(get_full_user_handler))
+        .route("/users", get(get_users_handler))
+        .with_state(ApiState { client })
+}
+
+/// Serves the control API on `addr` until `shutdown` resolves.
+pub async fn serve(
+    client: ClientWatch,
+    addr: SocketAddr,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, router(client))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_user_from_query_rejects_invalid_base64() {
+        assert!(packed_user_from_query("not valid base64!!").is_err());
+    }
+
+    #[test]
+    fn packed_user_from_query_rejects_invalid_packed_bytes() {
+        // Valid base64, but not a well-formed packed peer.
+        let id = base64::encode([1, 2, 3]);
+        assert!(packed_user_from_query(&id).is_err());
+    }
+}
@@ -1,17 +1,35 @@
 use grammers_client::types::Chat;
-use grammers_client::{Client, Config, InitParams, SignInError, Update};
+use grammers_client::{Client, Config, InitParams, Update};
 use grammers_mtsender::InvocationError;
 use grammers_session::Session;
 use grammers_tl_types as tl;
-use log::{self, error, info, warn};
+use log::{self, error, warn};
+#[cfg(feature = "http-api")]
+use log::info;
 use simple_logger::SimpleLogger;
-use std::env;
 use std::io::{self, BufRead as _, Write as _};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::watch;
 use tokio::{runtime, task};
 
+mod auth;
+mod config;
+mod dispatcher;
+#[cfg(feature = "http-api")]
+mod http_api;
+mod reconnect;
+mod relay;
+
+use auth::AuthMethod;
+use config::AppConfig;
+use dispatcher::{chat_kind, command, Dispatcher};
+use reconnect::{ConnectionStatus, ExponentialBackoff};
+use relay::Bridge;
+
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-const SESSION_FILE: &str = "app.session";
+const CONFIG_FILE: &str = "config.toml";
 
 fn prompt(message: &str) -> Result<String> {
     let stdout = io::stdout();
@@ -28,21 +46,23 @@ fn prompt(message: &str) -> Result<String> {
 }
 
 async fn async_main() -> Result<()> {
+    let config = AppConfig::parse(CONFIG_FILE)?;
+
     SimpleLogger::new()
-        .with_level(log::LevelFilter::Warn)
+        .with_level(config.log_level_filter())
         .init()
         .unwrap();
 
-    let api_id = env!("TG_ID").parse().expect("TG_ID invalid");
-    let api_hash = env!("TG_HASH").to_string();
+    let api_id = config.api_id;
+    let api_hash = config.api_hash.clone();
 
     println!("Connecting to Telegram...");
     let mut client = Client::connect(Config {
-        session: Session::load_file_or_create(SESSION_FILE)?,
+        session: Session::load_file_or_create(&config.session_file)?,
         api_id,
         api_hash: api_hash.clone(),
         params: InitParams {
-            proxy_url: Some("socks5://127.0.0.1:1086".to_string()),
+            proxy_url: config.proxy_url.clone(),
             ..Default::default()
         },
     })
@@ -54,27 +74,10 @@ async fn async_main() -> Result<()> {
 
     if !client.is_authorized().await? {
         println!("Signing in...");
-        let phone = prompt("Enter your phone number (international format): ")?;
-        let token = client.request_login_code(&phone, api_id, &api_hash).await?;
-        let code = prompt("Enter the code you received: ")?;
-        let signed_in = client.sign_in(&token, &code).await;
-        match signed_in {
-            Err(SignInError::PasswordRequired(password_token)) => {
-                // Note: this `prompt` method will echo the password in the console.
-                //       Real code might want to use a better way to handle this.
-                let hint = password_token.hint().unwrap();
-                let prompt_message = format!("Enter the password (hint {}): ", &hint);
-                let password = prompt(prompt_message.as_str())?;
-
-                client
-                    .check_password(password_token, password.trim())
-                    .await?;
-            }
-            Ok(_) => (),
-            Err(e) => panic!("{}", e),
-        };
+        let auth_method = AuthMethod::new(config.bot_token.clone());
+        auth::sign_in(&mut client, api_id, &api_hash, &auth_method).await?;
         println!("Signed in!");
-        match client.session().save_to_file(SESSION_FILE) {
+        match client.session().save_to_file(&config.session_file) {
             Ok(_) => {}
             Err(e) => {
                 println!(
@@ -86,45 +89,153 @@ async fn async_main() -> Result<()> {
         }
     }
 
-    // Obtain a `ClientHandle` to perform remote calls while `Client` drives the connection.
-    //
-    // This handle can be `clone()`'d around and freely moved into other tasks, so you can invoke
-    // methods concurrently if you need to. While you do this, the single owned `client` is the
-    // one that communicates with the network.
-    //
-    // The design's annoying to use for trivial sequential tasks, but is otherwise scalable.
-    let mut client_handle = client.clone();
-    let network_handle = task::spawn(async move { client.run_until_disconnected().await });
-
-    while let Some(update) = client_handle.next_update().await? {
-        if let Update::NewMessage(message) = update {
-            let _msg_id = message.id();
-            let _input_peer = message.chat().pack().to_bytes();
-            match message.chat() {
-                Chat::Group(_group) => {
-                    if let Some(Chat::User(user)) = message.sender() {
-                        // if !user.0.min {
-                        let input_user = user.pack().try_to_input_user().unwrap();
-                        match get_full_user(client_handle.clone(), input_user).await {
-                            Ok(_user) => {
-                                warn!("get_full_user success");
-                            }
-                            Err(error) => error!("{}", error.to_string()),
-                        }
-                        // }
-                    }
+    // The live `Client` is published on a `watch` channel instead of being
+    // cloned once into a plain variable: `run_supervised` replaces it with a
+    // fresh handle on every reconnect, and the dispatcher/HTTP API re-read
+    // the current value on each use rather than being stuck with whichever
+    // handle they happened to clone at startup.
+    let (client_tx, client_rx) = watch::channel(client);
+    let connection_status = ConnectionStatus::new();
+    let network_handle = {
+        let connection_status = connection_status.clone();
+        let session_file = config.session_file.clone();
+        let proxy_url = config.proxy_url.clone();
+        let config_template = move |server_addr: Option<SocketAddr>| Config {
+            session: Session::load_file_or_create(&session_file)
+                .expect("saved session must still be readable across reconnects"),
+            api_id,
+            api_hash: api_hash.clone(),
+            params: InitParams {
+                proxy_url: proxy_url.clone(),
+                server_addr,
+                ..Default::default()
+            },
+        };
+        task::spawn(async move {
+            let policy = ExponentialBackoff::default();
+            reconnect::run_supervised(client_tx, config_template, &policy, connection_status).await
+        })
+    };
+
+    let handler_status = connection_status.clone();
+    let mut dispatcher_builder = Dispatcher::builder(client_rx.clone())
+        .on_new_message(move |client, update| {
+            let status = handler_status.clone();
+            async move { handle_group_message(client, update, status).await }
+        })
+        .filter(chat_kind(|chat| matches!(chat, Chat::Group(_))))
+        .on_new_message(handle_start_command)
+        .filter(command("/start"));
+
+    // Relay mode is entirely optional: it only turns on when the config
+    // lists chats to bridge together.
+    if !config.bridge_chats.is_empty() {
+        let bridge = Bridge::new(config.bridge_chats.clone());
+
+        let message_bridge = bridge.clone();
+        dispatcher_builder = dispatcher_builder.on_new_message(move |client, update| {
+            let bridge = message_bridge.clone();
+            async move {
+                if let Update::NewMessage(message) = update.as_ref() {
+                    relay::relay_message(client, message, &bridge).await?;
+                }
+                Ok(())
+            }
+        });
+
+        dispatcher_builder = dispatcher_builder.on_raw_update(move |client, update| {
+            let bridge = bridge.clone();
+            async move {
+                if let Update::Raw(raw) = update.as_ref() {
+                    relay::relay_membership(client, raw, &bridge).await?;
                 }
-                _ => {}
+                Ok(())
             }
+        });
+    }
+
+    let dispatcher = dispatcher_builder.build();
+
+    // `http_shutdown_tx` lets us ask axum to drain in-flight requests and
+    // stop, instead of `.abort()`-ing the task and cutting them off.
+    #[cfg(feature = "http-api")]
+    let (http_shutdown_tx, http_shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    #[cfg(feature = "http-api")]
+    let http_handle = {
+        let client_rx = client_rx.clone();
+        task::spawn(async move {
+            let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+            http_api::serve(client_rx, addr, async move {
+                let _ = http_shutdown_rx.await;
+            })
+            .await
+        })
+    };
+
+    let mut network_handle = network_handle;
+    tokio::select! {
+        result = dispatcher.run() => { result?; }
+        result = &mut network_handle => { result??; }
+        _ = tokio::signal::ctrl_c() => {
+            println!("Ctrl-C received, shutting down...");
+        }
+    }
+    network_handle.abort();
+
+    #[cfg(feature = "http-api")]
+    {
+        let _ = http_shutdown_tx.send(());
+        match http_handle.await {
+            Ok(Ok(())) => info!("HTTP control API stopped cleanly"),
+            Ok(Err(e)) => error!("HTTP control API exited with an error: {}", e),
+            Err(e) => error!("HTTP control API task panicked: {}", e),
         }
     }
 
     if sign_out {
         // TODO revisit examples and get rid of "handle references" (also, this panics)
-        drop(client_handle.sign_out_disconnect().await);
+        drop(client_rx.borrow().clone().sign_out_disconnect().await);
     }
 
-    network_handle.await??;
+    Ok(())
+}
+
+/// Default handler for `NewMessage` updates in bridged/plain groups: looks up
+/// the sender's full user info. Registered with the [`dispatcher`] instead of
+/// being inlined in the update loop. Skips work while the connection is
+/// reconnecting, since `client` may be talking to a client that's about to
+/// be torn down.
+async fn handle_group_message(
+    client: Client,
+    update: Arc<Update>,
+    status: ConnectionStatus,
+) -> Result<()> {
+    if status.get() != reconnect::ConnectionState::Connected {
+        return Ok(());
+    }
+    let Update::NewMessage(message) = update.as_ref() else {
+        return Ok(());
+    };
+    if let Some(Chat::User(user)) = message.sender() {
+        let input_user = user.pack().try_to_input_user().unwrap();
+        match get_full_user(client, input_user).await {
+            Ok(_user) => {
+                warn!("get_full_user success");
+            }
+            Err(error) => error!("{}", error.to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Example `/start` command handler, registered with [`dispatcher::command`]
+/// to demonstrate filtering on more than just chat kind.
+async fn handle_start_command(client: Client, update: Arc<Update>) -> Result<()> {
+    if let Update::NewMessage(message) = update.as_ref() {
+        client
+            .send_message(message.chat().pack(), "Hello! This bot is online.")
+            .await?;
+    }
     Ok(())
 }
 
@@ -137,7 +248,7 @@ async fn get_full_user(
         .await
 }
 
-#[allow(dead_code)]
+#[cfg_attr(not(feature = "http-api"), allow(dead_code))]
 async fn get_users(
     client: Client,
     id: Vec<tl::enums::InputUser>,
@@ -0,0 +1,253 @@
+//! Supervises the network task across disconnects instead of letting the
+//! program wind down the moment `run_until_disconnected` returns. A
+//! [`ReconnectionPolicy`] decides whether/when to retry, backing off with
+//! jitter and resetting only once a session has stayed up past
+//! [`STABLE_AFTER`]. The live `Client` is published through a
+//! `watch` channel rather than a plain local variable, so every reconnect
+//! hands out a fresh handle instead of leaving other tasks (the dispatcher,
+//! the HTTP control API) talking to a dead connection. Once one address has
+//! failed repeatedly in a row, subsequent attempts fall back to trying the
+//! next known datacenter address instead of hammering the same one forever.
+
+use crate::Result;
+use grammers_client::{Client, Config};
+use log::{info, warn};
+use rand::Rng;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Receiver side of the `Client` channel: always holds the most recently
+/// published handle. Consumers should call `.borrow().clone()` fresh on
+/// each use rather than caching the clone across reconnects.
+pub type ClientWatch = watch::Receiver<Client>;
+
+/// Observable connection state, shared with handlers so they can pause work
+/// (e.g. stop relaying) while the client is reconnecting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ConnectionState {
+    Connected = 0,
+    Reconnecting = 1,
+    Fatal = 2,
+}
+
+/// Cheaply `clone()`-able handle to the current [`ConnectionState`].
+#[derive(Clone)]
+pub struct ConnectionStatus(Arc<AtomicU8>);
+
+impl ConnectionStatus {
+    pub fn new() -> Self {
+        ConnectionStatus(Arc::new(AtomicU8::new(ConnectionState::Connected as u8)))
+    }
+
+    fn set(&self, state: ConnectionState) {
+        self.0.store(state as u8, Ordering::SeqCst);
+    }
+
+    pub fn get(&self) -> ConnectionState {
+        match self.0.load(Ordering::SeqCst) {
+            0 => ConnectionState::Connected,
+            1 => ConnectionState::Reconnecting,
+            _ => ConnectionState::Fatal,
+        }
+    }
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decides whether (and how long) to wait before the next reconnect
+/// attempt. `attempt` is 0 for the first retry after a disconnect and keeps
+/// climbing until [`ReconnectionPolicy::next_delay`] returns `None`, at
+/// which point the supervisor gives up.
+pub trait ReconnectionPolicy: Send + Sync {
+    fn next_delay(&self, attempt: u32) -> Option<Duration>;
+}
+
+/// `delay = min(cap, base * 2^attempt)`, plus up to 20% random jitter so a
+/// fleet of reconnecting clients doesn't thunder the same server at once.
+pub struct ExponentialBackoff {
+    pub base: Duration,
+    pub cap: Duration,
+    pub max_attempts: u32,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        ExponentialBackoff {
+            base: Duration::from_secs(1),
+            cap: Duration::from_secs(60),
+            max_attempts: 10,
+        }
+    }
+}
+
+impl ReconnectionPolicy for ExponentialBackoff {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let exp = self.base.as_millis().saturating_mul(1u128 << attempt.min(32));
+        let capped = exp.min(self.cap.as_millis()) as u64;
+        let jitter = rand::thread_rng().gen_range(0..=capped / 5);
+        Some(Duration::from_millis(capped + jitter))
+    }
+}
+
+/// A session that stays up at least this long resets the backoff attempt
+/// counter, so a flaky reconnect doesn't carry a long delay into an
+/// otherwise healthy run. This is the *only* place `attempt` resets: a
+/// transport-level reconnect that immediately drops again (banned IP, bad
+/// session, flood-wait) must keep climbing the backoff instead of being
+/// quietly reset just because `Client::connect` itself succeeded.
+const STABLE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Telegram production datacenter addresses, tried in order once the
+/// address currently in use (whatever the saved session already points at)
+/// has failed [`ATTEMPTS_PER_DC`] reconnect attempts in a row. These are the
+/// same public addresses official clients fall back to.
+const DC_ADDRESSES: &[&str] = &[
+    "149.154.175.50:443",
+    "149.154.167.51:443",
+    "149.154.175.100:443",
+    "149.154.167.91:443",
+    "91.108.56.130:443",
+];
+
+/// How many consecutive failed reconnect attempts (a failed connect, or a
+/// connection that drops again before [`STABLE_AFTER`]) against one address
+/// before falling back to the next one in [`DC_ADDRESSES`].
+const ATTEMPTS_PER_DC: u32 = 3;
+
+/// Picks the fallback address to retry against, once `attempt` has climbed
+/// past the first batch of [`ATTEMPTS_PER_DC`] failures. Returns `None`
+/// while still within that first batch, so the first few attempts keep
+/// using whatever address the saved session already encodes; only
+/// subsequent batches of failures cycle through [`DC_ADDRESSES`].
+fn dc_fallback_address(attempt: u32) -> Option<SocketAddr> {
+    if attempt < ATTEMPTS_PER_DC {
+        return None;
+    }
+    let dc_index = (attempt / ATTEMPTS_PER_DC - 1) as usize % DC_ADDRESSES.len();
+    DC_ADDRESSES[dc_index].parse().ok()
+}
+
+/// Runs the client published on `client_tx` until disconnected, then keeps
+/// reconnecting (rebuilding the `Client` from `config_template`, which
+/// should load the same saved [`grammers_session::Session`] each time, and
+/// is passed a fallback address once repeated attempts fail) according to
+/// `policy`, updating `status` as it goes and re-publishing each new handle
+/// on `client_tx` so other tasks pick it up. Returns an error once the
+/// policy gives up.
+pub async fn run_supervised(
+    client_tx: watch::Sender<Client>,
+    config_template: impl Fn(Option<SocketAddr>) -> Config,
+    policy: &dyn ReconnectionPolicy,
+    status: ConnectionStatus,
+) -> Result<()> {
+    let mut attempt = 0u32;
+
+    loop {
+        status.set(ConnectionState::Connected);
+        let connected_at = Instant::now();
+        let client = client_tx.borrow().clone();
+        match client.run_until_disconnected().await {
+            Ok(()) => info!("connection closed cleanly"),
+            Err(e) => warn!("connection lost: {}", e),
+        }
+
+        if connected_at.elapsed() >= STABLE_AFTER {
+            attempt = 0;
+        }
+
+        status.set(ConnectionState::Reconnecting);
+        let Some(delay) = policy.next_delay(attempt) else {
+            status.set(ConnectionState::Fatal);
+            return Err("giving up reconnecting after repeated failures".into());
+        };
+
+        let dc_override = dc_fallback_address(attempt);
+        match dc_override {
+            Some(addr) => warn!(
+                "reconnecting in {:?} against fallback address {} (attempt {})",
+                delay, addr, attempt
+            ),
+            None => warn!("reconnecting in {:?} (attempt {})", delay, attempt),
+        }
+        tokio::time::sleep(delay).await;
+
+        match Client::connect(config_template(dc_override)).await {
+            Ok(new_client) => {
+                if client_tx.send(new_client).is_err() {
+                    // No receivers left means every consumer has been
+                    // dropped; nothing left to serve.
+                    return Ok(());
+                }
+            }
+            Err(e) => {
+                warn!("reconnect attempt {} failed: {}", attempt, e);
+            }
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially_and_caps() {
+        let policy = ExponentialBackoff {
+            base: Duration::from_secs(1),
+            cap: Duration::from_secs(10),
+            max_attempts: 10,
+        };
+        // Jitter adds up to 20%, so check the uncapped range [base*2^n, base*2^n*1.2].
+        let delay0 = policy.next_delay(0).unwrap();
+        assert!(delay0 >= Duration::from_secs(1) && delay0 <= Duration::from_millis(1200));
+
+        let delay2 = policy.next_delay(2).unwrap();
+        assert!(delay2 >= Duration::from_secs(4) && delay2 <= Duration::from_millis(4800));
+
+        // attempt 5 would be base*32 = 32s uncapped, but the cap is 10s.
+        let delay5 = policy.next_delay(5).unwrap();
+        assert!(delay5 >= Duration::from_secs(10) && delay5 <= Duration::from_millis(12000));
+    }
+
+    #[test]
+    fn backoff_gives_up_past_max_attempts() {
+        let policy = ExponentialBackoff {
+            base: Duration::from_millis(1),
+            cap: Duration::from_secs(1),
+            max_attempts: 3,
+        };
+        assert!(policy.next_delay(0).is_some());
+        assert!(policy.next_delay(2).is_some());
+        assert!(policy.next_delay(3).is_none());
+        assert!(policy.next_delay(100).is_none());
+    }
+
+    #[test]
+    fn dc_fallback_stays_on_default_address_for_first_batch() {
+        assert_eq!(dc_fallback_address(0), None);
+        assert_eq!(dc_fallback_address(ATTEMPTS_PER_DC - 1), None);
+    }
+
+    #[test]
+    fn dc_fallback_cycles_through_known_addresses() {
+        let first = dc_fallback_address(ATTEMPTS_PER_DC).unwrap();
+        let second = dc_fallback_address(2 * ATTEMPTS_PER_DC).unwrap();
+        assert_ne!(first, second);
+
+        // Cycles back around once every address has been tried.
+        let wrapped = dc_fallback_address(ATTEMPTS_PER_DC * (DC_ADDRESSES.len() as u32 + 1));
+        assert_eq!(wrapped, Some(first));
+    }
+}
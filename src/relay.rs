@@ -0,0 +1,262 @@
+//! Optional cross-chat relay ("bridge") mode: messages sent in any bridged
+//! group are re-sent to every other bridged group, prefixed with the
+//! sender's display name (resolved via `get_full_user` the first time a
+//! sender is seen, then cached, so repeated messages from the same sender
+//! don't redo the RPC), and member joins/leaves are announced across the
+//! bridge. Recreates a budget chat-relay bot on top of Telegram groups.
+
+use crate::Result;
+use grammers_client::types::{Chat, Message};
+use grammers_client::{Client, Update};
+use grammers_session::PackedChat;
+use grammers_tl_types as tl;
+use log::error;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct BridgeState {
+    /// Chat ids the bridge is configured to cover, whether or not we've
+    /// seen (and therefore packed) them yet.
+    wanted: HashSet<i64>,
+    /// Chats we've actually observed an update from, ready to send to.
+    known: HashMap<i64, PackedChat>,
+    /// Resolved display names, keyed by user id, shared across all bridged
+    /// chats so we don't re-resolve the same sender for every message.
+    names: HashMap<i64, String>,
+    /// Best-effort roster per chat, built up from observed messages and
+    /// join/leave events.
+    roster: HashMap<i64, HashSet<i64>>,
+}
+
+/// Shared bridge state, safe to hold across the concurrently-spawned
+/// handler tasks the dispatcher runs.
+#[derive(Clone)]
+pub struct Bridge(Arc<Mutex<BridgeState>>);
+
+impl Bridge {
+    pub fn new(chat_ids: impl IntoIterator<Item = i64>) -> Self {
+        Bridge(Arc::new(Mutex::new(BridgeState {
+            wanted: chat_ids.into_iter().collect(),
+            ..Default::default()
+        })))
+    }
+
+    fn is_bridged(&self, chat_id: i64) -> bool {
+        self.0.lock().unwrap().wanted.contains(&chat_id)
+    }
+
+    /// Records that `chat` is alive and reachable, returning `true` the
+    /// first time this chat is seen (i.e. it just became bridged).
+    fn note_chat(&self, chat: &Chat) -> bool {
+        let mut state = self.0.lock().unwrap();
+        if !state.wanted.contains(&chat.id()) {
+            return false;
+        }
+        state.roster.entry(chat.id()).or_default();
+        state.known.insert(chat.id(), chat.pack()).is_none()
+    }
+
+    fn other_chats(&self, from: i64) -> Vec<PackedChat> {
+        self.0
+            .lock()
+            .unwrap()
+            .known
+            .iter()
+            .filter(|(&id, _)| id != from)
+            .map(|(_, packed)| *packed)
+            .collect()
+    }
+
+    fn cached_name(&self, user_id: i64) -> Option<String> {
+        self.0.lock().unwrap().names.get(&user_id).cloned()
+    }
+
+    fn cache_name(&self, user_id: i64, name: String) {
+        self.0.lock().unwrap().names.insert(user_id, name);
+    }
+
+    fn note_member(&self, chat_id: i64, user_id: i64) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .roster
+            .entry(chat_id)
+            .or_default()
+            .insert(user_id)
+    }
+
+    fn forget_member(&self, chat_id: i64, user_id: i64) {
+        if let Some(roster) = self.0.lock().unwrap().roster.get_mut(&chat_id) {
+            roster.remove(&user_id);
+        }
+    }
+
+    fn roster_names(&self, chat_id: i64) -> Vec<String> {
+        let state = self.0.lock().unwrap();
+        state
+            .roster
+            .get(&chat_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| state.names.get(id).cloned())
+            .collect()
+    }
+}
+
+/// Resolves a sender's display name via `get_full_user`, caching the result
+/// so the RPC only fires once per sender. Falls back to the name already
+/// present on the update (`full_name()`) when the lookup fails or doesn't
+/// carry a usable name.
+async fn display_name(client: &Client, bridge: &Bridge, user: &grammers_client::types::User) -> String {
+    if let Some(name) = bridge.cached_name(user.id()) {
+        return name;
+    }
+    let name = resolve_display_name(client, user).await;
+    bridge.cache_name(user.id(), name.clone());
+    name
+}
+
+async fn resolve_display_name(client: &Client, user: &grammers_client::types::User) -> String {
+    let Some(input_user) = user.pack().try_to_input_user() else {
+        return user.full_name();
+    };
+    match crate::get_full_user(client.clone(), input_user).await {
+        Ok(full) => full_user_name(&full, user.id()).unwrap_or_else(|| user.full_name()),
+        Err(e) => {
+            error!("get_full_user failed while resolving display name: {}", e);
+            user.full_name()
+        }
+    }
+}
+
+/// Pulls `first_name`/`last_name` for `user_id` out of a resolved
+/// `UserFull` response, returning `None` if the user isn't present or has
+/// neither name field set.
+fn full_user_name(full: &tl::enums::users::UserFull, user_id: i64) -> Option<String> {
+    let tl::enums::users::UserFull::Full(full) = full else {
+        return None;
+    };
+    full.users.iter().find_map(|u| match u {
+        tl::enums::User::User(user) if user.id == user_id => {
+            let name = [user.first_name.as_deref(), user.last_name.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ");
+            (!name.is_empty()).then_some(name)
+        }
+        _ => None,
+    })
+}
+
+/// Sends `text` to every bridged chat other than `from`, logging (but not
+/// failing the caller on) per-target send errors.
+async fn broadcast(client: &Client, bridge: &Bridge, from: i64, text: &str) {
+    for target in bridge.other_chats(from) {
+        if let Err(e) = client.send_message(target, text).await {
+            error!("relay send to {:?} failed: {}", target, e);
+        }
+    }
+}
+
+/// Handles a `NewMessage` update for a (potentially) bridged chat: relays
+/// the message to the rest of the bridge, announcing the roster first if
+/// this chat was just newly bridged.
+pub async fn relay_message(client: Client, message: &Message, bridge: &Bridge) -> Result<()> {
+    let chat = message.chat();
+    if !bridge.is_bridged(chat.id()) {
+        return Ok(());
+    }
+
+    if bridge.note_chat(&chat) {
+        let roster = bridge.roster_names(chat.id());
+        if !roster.is_empty() {
+            let text = format!("* current members: {}", roster.join(", "));
+            let _ = client.send_message(chat.pack(), text.as_str()).await;
+        }
+    }
+
+    let name = match message.sender() {
+        Some(Chat::User(user)) => {
+            bridge.note_member(chat.id(), user.id());
+            display_name(&client, bridge, &user).await
+        }
+        _ => "someone".to_string(),
+    };
+
+    let text = format!("[{}] {}", name, message.text());
+    broadcast(&client, bridge, chat.id(), &text).await;
+    Ok(())
+}
+
+/// Handles raw updates, watching for chat membership changes so the bridge
+/// can announce joins/leaves across the other bridged chats.
+pub async fn relay_membership(client: Client, update: &tl::enums::Update, bridge: &Bridge) -> Result<()> {
+    let (chat_id, user_id, joined) = match update {
+        tl::enums::Update::ChatParticipantAdd(u) => (u.chat_id, u.user_id, true),
+        tl::enums::Update::ChatParticipantDelete(u) => (u.chat_id, u.user_id, false),
+        _ => return Ok(()),
+    };
+    if !bridge.is_bridged(chat_id) {
+        return Ok(());
+    }
+
+    let name = bridge
+        .cached_name(user_id)
+        .unwrap_or_else(|| format!("user {}", user_id));
+    if joined {
+        bridge.note_member(chat_id, user_id);
+    } else {
+        bridge.forget_member(chat_id, user_id);
+    }
+
+    let verb = if joined { "joined" } else { "left" };
+    let text = format!("* {} {}", name, verb);
+    broadcast(&client, bridge, chat_id, &text).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_bridged_only_covers_configured_chats() {
+        let bridge = Bridge::new([1, 2]);
+        assert!(bridge.is_bridged(1));
+        assert!(bridge.is_bridged(2));
+        assert!(!bridge.is_bridged(3));
+    }
+
+    #[test]
+    fn name_cache_round_trips() {
+        let bridge = Bridge::new([1]);
+        assert_eq!(bridge.cached_name(42), None);
+        bridge.cache_name(42, "Ada".to_string());
+        assert_eq!(bridge.cached_name(42), Some("Ada".to_string()));
+    }
+
+    #[test]
+    fn roster_tracks_joins_and_leaves() {
+        let bridge = Bridge::new([1]);
+        bridge.cache_name(42, "Ada".to_string());
+        bridge.cache_name(7, "Grace".to_string());
+
+        bridge.note_member(1, 42);
+        bridge.note_member(1, 7);
+        let mut names = bridge.roster_names(1);
+        names.sort();
+        assert_eq!(names, vec!["Ada".to_string(), "Grace".to_string()]);
+
+        bridge.forget_member(1, 7);
+        assert_eq!(bridge.roster_names(1), vec!["Ada".to_string()]);
+    }
+
+    #[test]
+    fn roster_names_skips_unresolved_members() {
+        let bridge = Bridge::new([1]);
+        bridge.note_member(1, 99);
+        assert!(bridge.roster_names(1).is_empty());
+    }
+}